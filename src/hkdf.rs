@@ -0,0 +1,80 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#![allow(non_camel_case_types)]
+
+use crate::hmac::hmac;
+use crate::hmac::hmac_alg_to_hmac_len;
+use crate::hmac::HmacAlgorithm;
+use crate::Error;
+
+//
+// HKDF (RFC 5869) extract-and-expand, layered on top of `hmac()`.
+//
+
+/// HKDF-Extract: `PRK = HMAC(salt, IKM)`.
+///
+/// When `salt` is empty a string of `HashLen` zero bytes is used, as
+/// specified in RFC 5869 section 2.2.
+pub fn hkdf_extract(alg: &HmacAlgorithm, salt: &[u8], ikm: &[u8]) -> Result<Vec<u8>, Error> {
+    let hash_len = hmac_alg_to_hmac_len(alg)?;
+    let zero_salt;
+    let salt = if salt.is_empty() {
+        zero_salt = vec![0u8; hash_len];
+        zero_salt.as_slice()
+    } else {
+        salt
+    };
+    hmac(alg, salt, ikm)
+}
+
+/// HKDF-Expand: derive `length` bytes of output keying material from `prk`.
+///
+/// `T(0)` is the empty string and `T(i) = HMAC(PRK, T(i-1) || info || i)`
+/// for `i = 1..`; `T(1) || T(2) || ...` is truncated to `length`. Requests
+/// for more than `255 * HashLen` bytes are rejected, as mandated by RFC 5869
+/// section 2.3.
+pub fn hkdf_expand(
+    alg: &HmacAlgorithm,
+    prk: &[u8],
+    info: &[u8],
+    length: usize,
+) -> Result<Vec<u8>, Error> {
+    let hash_len = hmac_alg_to_hmac_len(alg)?;
+    if length > 255 * hash_len {
+        return Err(Error::InternalError);
+    }
+
+    let mut okm = Vec::with_capacity(length);
+    let mut t: Vec<u8> = Vec::new();
+    let mut counter: u8 = 1;
+    while okm.len() < length {
+        let mut block = Vec::with_capacity(t.len() + info.len() + 1);
+        block.extend_from_slice(&t);
+        block.extend_from_slice(info);
+        block.push(counter);
+        t = hmac(alg, prk, &block)?;
+        okm.extend_from_slice(&t);
+        counter = match counter.checked_add(1) {
+            Some(counter) => counter,
+            None => break,
+        };
+    }
+    okm.truncate(length);
+    Ok(okm)
+}
+
+/// Convenience wrapper running HKDF-Extract followed by HKDF-Expand.
+pub fn hkdf(
+    alg: &HmacAlgorithm,
+    salt: &[u8],
+    info: &[u8],
+    ikm: &[u8],
+    length: usize,
+) -> Result<Vec<u8>, Error> {
+    let prk = hkdf_extract(alg, salt, ikm)?;
+    hkdf_expand(alg, &prk, info, length)
+}